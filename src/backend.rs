@@ -0,0 +1,198 @@
+use std::io::{self, Write};
+use std::num::NonZero;
+use std::process::{Child, Command, Stdio};
+
+use rodio::cpal::BufferSize;
+use rodio::cpal::traits::HostTrait;
+use rodio::mixer::{Mixer, MixerSource, mixer};
+
+/// Selects where mixed audio actually goes. `Cpal` opens a real output
+/// device; `Pipe` and `Subprocess` drive no audio device at all, which is
+/// what headless servers, tests, and transcoding pipelines need instead of
+/// a panic when no device is available.
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Cpal,
+    /// Writes interleaved `i16` PCM to stdout.
+    Pipe,
+    /// Writes interleaved `i16` PCM to the stdin of a spawned command, e.g.
+    /// `ffplay -autoexit -`.
+    Subprocess(String),
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::Cpal
+    }
+}
+
+impl Backend {
+    /// Parses the `--backend` CLI flag: `cpal`, `pipe`, or
+    /// `subprocess:<command>`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "cpal" => Some(Self::Cpal),
+            "pipe" => Some(Self::Pipe),
+            _ => name
+                .strip_prefix("subprocess:")
+                .map(|command| Self::Subprocess(command.to_string())),
+        }
+    }
+}
+
+/// The open output stream. Dropping it tears down whatever resources the
+/// chosen backend needed (cpal stream, writer thread, child process).
+pub struct OutputStream {
+    mixer: Mixer,
+    sample_rate: NonZero<u32>,
+    /// Never read; kept only so its `Drop` impl tears down the backend's
+    /// resources (cpal stream, child process) for as long as `OutputStream`
+    /// is alive.
+    #[allow(dead_code)]
+    kind: OutputStreamKind,
+}
+
+enum OutputStreamKind {
+    /// `MixerDeviceSink`'s payload is likewise never read; held for `Drop`.
+    Cpal(#[allow(dead_code)] rodio::MixerDeviceSink),
+    Writer { _child: Option<Child> },
+}
+
+impl Drop for OutputStreamKind {
+    fn drop(&mut self) {
+        // `Child` has no `Drop` of its own; without this the process outlives
+        // the stream.
+        if let OutputStreamKind::Writer { _child: Some(child) } = self {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl OutputStream {
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    pub fn sample_rate(&self) -> NonZero<u32> {
+        self.sample_rate
+    }
+}
+
+pub fn open_stream(
+    backend: &Backend,
+    sample_rate: NonZero<u32>,
+    channels: NonZero<u16>,
+) -> io::Result<OutputStream> {
+    match backend {
+        Backend::Cpal => {
+            let mut sink = open_cpal_device(sample_rate)?;
+            sink.log_on_drop(false);
+            let mixer = sink.mixer().clone();
+
+            Ok(OutputStream {
+                mixer,
+                sample_rate,
+                kind: OutputStreamKind::Cpal(sink),
+            })
+        }
+        Backend::Pipe => {
+            let (output_mixer, source) = mixer(channels, sample_rate);
+            spawn_writer(source, io::stdout());
+
+            Ok(OutputStream {
+                mixer: output_mixer,
+                sample_rate,
+                kind: OutputStreamKind::Writer { _child: None },
+            })
+        }
+        Backend::Subprocess(command) => {
+            let mut parts = command.split_whitespace();
+            let program = parts.next().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "empty subprocess command")
+            })?;
+
+            let mut child = Command::new(program)
+                .args(parts)
+                .stdin(Stdio::piped())
+                .spawn()?;
+            let stdin = child.stdin.take().expect("stdin is piped above");
+
+            let (output_mixer, source) = mixer(channels, sample_rate);
+            spawn_writer(source, stdin);
+
+            Ok(OutputStream {
+                mixer: output_mixer,
+                sample_rate,
+                kind: OutputStreamKind::Writer {
+                    _child: Some(child),
+                },
+            })
+        }
+    }
+}
+
+fn open_cpal_device(sample_rate: NonZero<u32>) -> io::Result<rodio::MixerDeviceSink> {
+    let primary = rodio::DeviceSinkBuilder::from_default_device().and_then(|x| {
+        x.with_sample_rate(sample_rate)
+            .with_buffer_size(BufferSize::Fixed(1024))
+            .open_stream()
+    });
+
+    let result = match primary {
+        Ok(stream) => Ok(stream),
+        Err(original_err) => rodio::cpal::default_host()
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| {
+                devices.find_map(|d| {
+                    rodio::DeviceSinkBuilder::from_device(d)
+                        .and_then(|x| {
+                            x.with_sample_rate(sample_rate)
+                                .with_buffer_size(BufferSize::Fixed(1024))
+                                .open_stream()
+                        })
+                        .ok()
+                })
+            })
+            .ok_or(original_err),
+    };
+
+    result.map_err(|err| io::Error::other(err.to_string()))
+}
+
+fn spawn_writer(mut source: MixerSource, mut writer: impl Write + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut sample_buf = [0u8; 2];
+        for sample in source.by_ref() {
+            sample_buf.copy_from_slice(&sample.to_le_bytes());
+            if writer.write_all(&sample_buf).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_backends() {
+        assert!(matches!(Backend::parse("cpal"), Some(Backend::Cpal)));
+        assert!(matches!(Backend::parse("pipe"), Some(Backend::Pipe)));
+    }
+
+    #[test]
+    fn parses_subprocess_command() {
+        match Backend::parse("subprocess:ffplay -autoexit -") {
+            Some(Backend::Subprocess(command)) => assert_eq!(command, "ffplay -autoexit -"),
+            other => panic!("expected Subprocess, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_backend() {
+        assert!(Backend::parse("winamp").is_none());
+    }
+}
@@ -0,0 +1,214 @@
+//! C ABI over [`Sink`], for embedding this engine in a Swift/Flutter
+//! frontend. Every `sink_*` function takes or returns an opaque
+//! `*mut SinkHandle` and reports failure through [`SinkStatus`] rather than
+//! unwinding across the FFI boundary, since a Rust panic unwinding into C is
+//! undefined behavior.
+
+use std::ffi::{CStr, c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use std::time::Duration;
+
+use tokio::runtime::Runtime;
+use tokio::task::AbortHandle;
+
+use crate::{QueryTrackResult, Sink};
+
+/// Result of a `sink_*` call, returned instead of panicking so a C caller
+/// can check it without a try/catch.
+#[repr(C)]
+pub enum SinkStatus {
+    Ok = 0,
+    InvalidHandle = 1,
+    InvalidPath = 2,
+    RecreateStreamRequired = 3,
+    Error = 4,
+}
+
+/// Opaque handle returned by [`sink_new`]. Bundles a [`Sink`] with the
+/// tokio runtime it needs: FFI callers are plain C/Swift/Dart with no
+/// runtime of their own, but `Sink` spawns background tasks (track
+/// auto-advance, duration resolution) that need one to run on.
+pub struct SinkHandle {
+    sink: Sink,
+    runtime: Runtime,
+    /// The task spawned by the last [`sink_set_track_finished_callback`]
+    /// call, aborted when a new one replaces it.
+    track_finished_callback: Option<AbortHandle>,
+}
+
+/// Invoked on the runtime's own thread when `Sink::track_finished` fires;
+/// register with [`sink_set_track_finished_callback`].
+pub type TrackFinishedCallback = extern "C" fn(user_data: *mut c_void);
+
+/// Wraps a `*mut c_void` so it can be moved into the spawned task below.
+/// Sound because the pointer is never dereferenced by us, only handed back
+/// to the caller's own callback.
+struct SendPtr(*mut c_void);
+unsafe impl Send for SendPtr {}
+
+/// Creates a new [`Sink`] and its runtime. Returns null if either fails to
+/// initialize.
+#[unsafe(no_mangle)]
+pub extern "C" fn sink_new() -> *mut SinkHandle {
+    let handle = panic::catch_unwind(|| {
+        let runtime = Runtime::new().ok()?;
+        Some(Box::new(SinkHandle {
+            sink: Sink::new(),
+            runtime,
+            track_finished_callback: None,
+        }))
+    });
+
+    match handle {
+        Ok(Some(handle)) => Box::into_raw(handle),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Destroys a handle created by [`sink_new`]. `handle` may be null.
+///
+/// # Safety
+/// `handle` must be a pointer returned by [`sink_new`] that hasn't already
+/// been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sink_free(handle: *mut SinkHandle) {
+    if handle.is_null() {
+        return;
+    }
+
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+        drop(Box::from_raw(handle));
+    }));
+}
+
+/// Queues `path` for playback, opening the output stream if needed.
+///
+/// # Safety
+/// `handle` must be a live pointer from [`sink_new`]; `path` must be a
+/// null-terminated, valid UTF-8 C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sink_query_track(
+    handle: *mut SinkHandle,
+    path: *const c_char,
+) -> SinkStatus {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return SinkStatus::InvalidHandle;
+    };
+    if path.is_null() {
+        return SinkStatus::InvalidPath;
+    }
+    let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+        return SinkStatus::InvalidPath;
+    };
+    let path = Path::new(path);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let SinkHandle { sink, runtime, .. } = handle;
+        runtime.block_on(async { sink.query_track(path) })
+    }));
+
+    match result {
+        Ok(Ok(QueryTrackResult::Queued(_))) => SinkStatus::Ok,
+        Ok(Ok(QueryTrackResult::RecreateStreamRequired)) => SinkStatus::RecreateStreamRequired,
+        Ok(Err(_)) | Err(_) => SinkStatus::Error,
+    }
+}
+
+/// # Safety
+/// `handle` must be a live pointer from [`sink_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sink_play(handle: *mut SinkHandle) -> SinkStatus {
+    let Some(handle) = (unsafe { handle.as_ref() }) else {
+        return SinkStatus::InvalidHandle;
+    };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| handle.sink.play())) {
+        Ok(()) => SinkStatus::Ok,
+        Err(_) => SinkStatus::Error,
+    }
+}
+
+/// # Safety
+/// `handle` must be a live pointer from [`sink_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sink_pause(handle: *mut SinkHandle) -> SinkStatus {
+    let Some(handle) = (unsafe { handle.as_ref() }) else {
+        return SinkStatus::InvalidHandle;
+    };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| handle.sink.pause())) {
+        Ok(()) => SinkStatus::Ok,
+        Err(_) => SinkStatus::Error,
+    }
+}
+
+/// # Safety
+/// `handle` must be a live pointer from [`sink_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sink_seek(handle: *mut SinkHandle, millis: u64) -> SinkStatus {
+    let Some(handle) = (unsafe { handle.as_ref() }) else {
+        return SinkStatus::InvalidHandle;
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        handle.sink.seek(Duration::from_millis(millis))
+    }));
+
+    match result {
+        Ok(()) => SinkStatus::Ok,
+        Err(_) => SinkStatus::Error,
+    }
+}
+
+/// Returns the current playback position in milliseconds, or 0 for an
+/// invalid handle.
+///
+/// # Safety
+/// `handle` must be a live pointer from [`sink_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sink_position_millis(handle: *mut SinkHandle) -> u64 {
+    let Some(handle) = (unsafe { handle.as_ref() }) else {
+        return 0;
+    };
+
+    panic::catch_unwind(AssertUnwindSafe(|| handle.sink.position().as_millis() as u64))
+        .unwrap_or(0)
+}
+
+/// Registers `callback` to be invoked with `user_data` on the handle's own
+/// runtime thread every time [`Sink::track_finished`] fires. Replaces any
+/// previously registered callback.
+///
+/// # Safety
+/// `handle` must be a live pointer from [`sink_new`]. `callback` must
+/// remain valid to call, and `user_data` must remain valid to pass to it,
+/// for as long as `handle` is alive or until a new callback is registered.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sink_set_track_finished_callback(
+    handle: *mut SinkHandle,
+    callback: TrackFinishedCallback,
+    user_data: *mut c_void,
+) -> SinkStatus {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return SinkStatus::InvalidHandle;
+    };
+
+    if let Some(previous) = handle.track_finished_callback.take() {
+        previous.abort();
+    }
+
+    let mut track_finished = handle.sink.track_finished();
+    let user_data = SendPtr(user_data);
+
+    let task = handle.runtime.spawn(async move {
+        let user_data = user_data;
+        while track_finished.changed().await.is_ok() {
+            callback(user_data.0);
+        }
+    });
+    handle.track_finished_callback = Some(task.abort_handle());
+
+    SinkStatus::Ok
+}
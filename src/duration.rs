@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use rodio::{Decoder, Source};
+use tokio::task::JoinHandle;
+
+/// Caches the real duration of tracks whose container doesn't report one
+/// (common for VBR MP3 and similar), so replaying them doesn't repeat the
+/// decode-and-count pass.
+#[derive(Clone, Default)]
+pub struct DurationCache {
+    known: Arc<Mutex<HashMap<PathBuf, Duration>>>,
+}
+
+impl DurationCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Duration> {
+        self.known.lock().get(path).copied()
+    }
+
+    /// Spawns a blocking task that decodes `path` a second time, counting
+    /// samples to derive its real duration, caching and returning it.
+    pub fn resolve(&self, path: PathBuf) -> JoinHandle<Option<Duration>> {
+        let cache = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let duration = count_duration(&path)?;
+            cache.known.lock().insert(path, duration);
+            Some(duration)
+        })
+    }
+}
+
+fn count_duration(path: &Path) -> Option<Duration> {
+    let file = fs::File::open(path).ok()?;
+    let source = Decoder::try_from(file).ok()?;
+
+    let sample_rate = source.sample_rate();
+    let channels = source.channels();
+    let sample_count = source.count() as u64;
+
+    Some(Duration::from_secs_f64(
+        sample_count as f64 / sample_rate.get() as f64 / channels.get() as f64,
+    ))
+}
@@ -1,26 +1,72 @@
 use std::fs;
+use std::io;
+use std::net::{SocketAddr, TcpStream};
 use std::num::NonZero;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
 
 use clap::Parser;
 use parking_lot::Mutex;
-use rodio::cpal::BufferSize;
-use rodio::cpal::traits::HostTrait;
 use rodio::queue::queue;
 use rodio::{Decoder, Source};
 use tokio::sync::watch::{self, Receiver, Sender};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
+mod backend;
+mod c;
+mod duration;
+mod metadata;
+mod network;
+mod playlist;
+mod resample;
+
+pub use backend::Backend;
+use backend::OutputStream;
+use duration::DurationCache;
+use metadata::TrackMetadata;
+use network::NetworkSource;
+pub use playlist::RepeatMode;
+use playlist::Playlist;
+use resample::LinearResampler;
+
+/// A decoded track, optionally resampled to the mixer's sample rate.
+type BoxedSource = Box<dyn Source<Item = i16> + Send>;
+
+/// Within this long of the current track's start, `previous()` restarts it
+/// instead of skipping back to the previous track.
+const PREVIOUS_RESTART_THRESHOLD: Duration = Duration::from_secs(3);
+
+/// Used immediately for tracks whose container doesn't report a duration,
+/// while the real figure is computed in the background.
+const ASSUMED_TRACK_DURATION: Duration = Duration::from_secs(150);
+
 pub struct Sink {
     player: Option<rodio::Player>,
-    mixer: Option<rodio::MixerDeviceSink>,
+    mixer: Option<OutputStream>,
     sender: Option<Arc<rodio::queue::SourcesQueueInput>>,
     track_finished: Sender<()>,
     track_handle: Option<JoinHandle<()>>,
     duration_played: Arc<Mutex<Duration>>,
+    network_stream: Option<TcpStream>,
+    current_metadata: Arc<Mutex<Option<TrackMetadata>>>,
+    metadata_changed: Sender<()>,
+    playlist: Playlist,
+    backend: Backend,
+    /// Duration of the track currently in flight, read by the background
+    /// task when the track finishes to advance `duration_played`. Starts
+    /// out possibly just a guess; see [`resolve_track_duration`].
+    current_track_duration: Arc<Mutex<Duration>>,
+    duration_cache: DurationCache,
+    resampling: Arc<AtomicBool>,
+    /// Bumped every time the currently-queued track changes. Captured by a
+    /// background duration resolution when it's spawned and checked again
+    /// before it writes back, so a resolution for a track that's since been
+    /// skipped past can't clobber `current_track_duration`/`current_metadata`
+    /// for whatever is playing now.
+    track_generation: Arc<AtomicU64>,
 }
 
 impl Default for Sink {
@@ -31,7 +77,14 @@ impl Default for Sink {
 
 impl Sink {
     pub fn new() -> Self {
+        Self::with_backend(Backend::default())
+    }
+
+    /// Like [`Self::new`], but routes mixed audio through `backend` instead
+    /// of the default cpal output device.
+    pub fn with_backend(backend: Backend) -> Self {
         let (track_finished, _) = watch::channel(());
+        let (metadata_changed, _) = watch::channel(());
         Self {
             player: None,
             mixer: None,
@@ -39,13 +92,83 @@ impl Sink {
             track_finished,
             track_handle: Default::default(),
             duration_played: Default::default(),
+            network_stream: None,
+            current_metadata: Default::default(),
+            metadata_changed,
+            playlist: Playlist::new(),
+            backend,
+            current_track_duration: Default::default(),
+            duration_cache: DurationCache::new(),
+            resampling: Default::default(),
+            track_generation: Default::default(),
         }
     }
 
+    /// When enabled, a track whose sample rate doesn't match the open
+    /// mixer is resampled on the fly instead of `query_track`/`query_stream`
+    /// returning [`QueryTrackResult::RecreateStreamRequired`], keeping
+    /// gapless playback across an album with mixed rates.
+    pub fn set_resampling(&self, enabled: bool) {
+        self.resampling.store(enabled, Ordering::Relaxed);
+    }
+
     pub fn track_finished(&self) -> Receiver<()> {
         self.track_finished.subscribe()
     }
 
+    /// Fires whenever the currently playing track changes, so a UI can
+    /// re-render without polling [`Self::metadata`].
+    pub fn metadata_changed(&self) -> Receiver<()> {
+        self.metadata_changed.subscribe()
+    }
+
+    pub fn metadata(&self) -> Option<TrackMetadata> {
+        self.current_metadata.lock().clone()
+    }
+
+    /// Adds tracks to the end of the playlist.
+    pub fn enqueue(&self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.playlist.enqueue(paths);
+    }
+
+    pub fn set_repeat(&self, mode: RepeatMode) {
+        self.playlist.set_repeat(mode);
+    }
+
+    pub fn set_shuffle(&self, shuffle: bool) {
+        self.playlist.set_shuffle(shuffle);
+    }
+
+    /// Skips to the next track in the playlist, per the current
+    /// [`RepeatMode`]. Returns `None` if the playlist is empty or has ended.
+    pub fn next(&mut self) -> Option<io::Result<QueryTrackResult>> {
+        let path = self.playlist.advance_next()?;
+        Some(self.skip_to(&path))
+    }
+
+    /// Within [`PREVIOUS_RESTART_THRESHOLD`] of a track's start this
+    /// restarts it; otherwise it skips to the previous track in the
+    /// playlist.
+    pub fn previous(&mut self) -> Option<io::Result<QueryTrackResult>> {
+        if self.position() < PREVIOUS_RESTART_THRESHOLD {
+            let path = self.playlist.current()?;
+            return Some(self.skip_to(&path));
+        }
+
+        let path = self.playlist.advance_previous()?;
+        Some(self.skip_to(&path))
+    }
+
+    /// Stops whatever is currently queued and queues `path` in its place.
+    fn skip_to(&mut self, path: &Path) -> io::Result<QueryTrackResult> {
+        self.clear_queue();
+        if let Some(handle) = self.track_handle.take() {
+            handle.abort();
+        }
+
+        self.query_track(path)
+    }
+
     pub fn position(&self) -> Duration {
         let position = self
             .player
@@ -106,41 +229,244 @@ impl Sink {
         self.player.is_none()
     }
 
-    pub fn query_track(&mut self, track_path: &Path) -> QueryTrackResult {
-        let file = fs::File::open(track_path).unwrap();
+    pub fn query_track(&mut self, track_path: &Path) -> io::Result<QueryTrackResult> {
+        let file = fs::File::open(track_path)?;
 
-        let source = Decoder::try_from(file).unwrap();
+        let source = Decoder::try_from(file).map_err(|err| io::Error::other(err.to_string()))?;
 
         let sample_rate = source.sample_rate();
+        let channels = source.channels();
+        let generation = self.track_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let track_duration = resolve_track_duration(
+            track_path,
+            source.total_duration(),
+            &self.duration_cache,
+            generation,
+            self.track_generation.clone(),
+            self.current_track_duration.clone(),
+            self.current_metadata.clone(),
+            self.metadata_changed.clone(),
+        );
+
         let same_sample_rate = self
             .mixer
             .as_ref()
-            .map(|mixer| mixer.config().sample_rate() == sample_rate)
+            .map(|stream| stream.sample_rate() == sample_rate)
             .unwrap_or(true);
 
-        if !same_sample_rate {
-            return QueryTrackResult::RecreateStreamRequired;
+        if !same_sample_rate && !self.resampling.load(Ordering::Relaxed) {
+            return Ok(QueryTrackResult::RecreateStreamRequired);
         }
 
         let needs_stream = self.mixer.is_none() || self.player.is_none();
 
         if needs_stream {
-            let mut mixer = open_default_stream(sample_rate);
-            mixer.log_on_drop(false);
+            let stream = backend::open_stream(&self.backend, sample_rate, channels)?;
 
             let (sender, receiver) = queue(true);
-            let player = rodio::Player::connect_new(mixer.mixer());
+            let player = rodio::Player::connect_new(stream.mixer());
             player.append(receiver);
             set_volume(&player, &1.0);
 
             self.player = Some(player);
             self.sender = Some(sender);
-            self.mixer = Some(mixer);
+            self.mixer = Some(stream);
         }
 
+        let mixer_sample_rate = self.mixer.as_ref().unwrap().sample_rate();
+        let source: BoxedSource = if sample_rate == mixer_sample_rate {
+            Box::new(source)
+        } else {
+            Box::new(LinearResampler::new(source, mixer_sample_rate))
+        };
+
+        *self.current_track_duration.lock() = track_duration;
+
+        let metadata = TrackMetadata::read(track_path, sample_rate, channels, track_duration);
+        *self.current_metadata.lock() = Some(metadata.clone());
+        let _ = self.metadata_changed.send(());
+
+        self.spawn_track_handle(source);
+
+        Ok(QueryTrackResult::Queued(metadata))
+    }
+
+    /// Appends `source` to the queue and spawns the task that waits for it
+    /// to finish, then auto-advances the playlist and appends the next
+    /// track, looping until the playlist runs out.
+    fn spawn_track_handle(&mut self, source: BoxedSource) {
+        let sender = self.sender.as_ref().unwrap().clone();
+        let mixer_sample_rate = self.mixer.as_ref().unwrap().sample_rate();
+        let playlist = self.playlist.clone();
         let track_finished = self.track_finished.clone();
+        let metadata_changed = self.metadata_changed.clone();
+        let current_metadata = self.current_metadata.clone();
+        let current_track_duration = self.current_track_duration.clone();
+        let duration_played = self.duration_played.clone();
+        let duration_cache = self.duration_cache.clone();
+        let resampling = self.resampling.clone();
+        let track_generation = self.track_generation.clone();
+
+        let mut signal = sender.append_with_signal(source);
+
+        let track_handle = tokio::spawn(async move {
+            loop {
+                loop {
+                    if signal.try_recv().is_ok() {
+                        *duration_played.lock() += *current_track_duration.lock();
+                        let _ = track_finished.send(());
+                        break;
+                    }
+                    sleep(Duration::from_millis(200)).await;
+                }
+
+                let Some(next_path) = playlist.advance_next() else {
+                    break;
+                };
+                let Ok(file) = fs::File::open(&next_path) else {
+                    break;
+                };
+                let Ok(next_source) = Decoder::try_from(file) else {
+                    break;
+                };
+
+                let next_sample_rate = next_source.sample_rate();
+                let next_channels = next_source.channels();
+
+                // A sample-rate change with resampling off would need to
+                // rebuild the mixer, which needs `&mut Sink` that this
+                // detached task doesn't have. Stop auto-advancing; the
+                // playlist's current position is already on `next_path`, but
+                // nothing is queued for it, and rebuilding the stream for its
+                // rate is left to the caller.
+                if next_sample_rate != mixer_sample_rate && !resampling.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let generation = track_generation.fetch_add(1, Ordering::SeqCst) + 1;
+                let next_duration = resolve_track_duration(
+                    &next_path,
+                    next_source.total_duration(),
+                    &duration_cache,
+                    generation,
+                    track_generation.clone(),
+                    current_track_duration.clone(),
+                    current_metadata.clone(),
+                    metadata_changed.clone(),
+                );
+                *current_track_duration.lock() = next_duration;
+
+                // TrackMetadata::read does synchronous file I/O and lofty
+                // tag/cover parsing; off the tokio worker thread, same as
+                // DurationCache::resolve above.
+                let metadata_path = next_path.clone();
+                let Ok(next_metadata) = tokio::task::spawn_blocking(move || {
+                    TrackMetadata::read(&metadata_path, next_sample_rate, next_channels, next_duration)
+                })
+                .await
+                else {
+                    break;
+                };
+
+                *current_metadata.lock() = Some(next_metadata);
+                let _ = metadata_changed.send(());
+
+                let next_source: BoxedSource = if next_sample_rate == mixer_sample_rate {
+                    Box::new(next_source)
+                } else {
+                    Box::new(LinearResampler::new(next_source, mixer_sample_rate))
+                };
+
+                signal = sender.append_with_signal(next_source);
+            }
+        });
+
+        self.track_handle = Some(track_handle);
+    }
+
+    /// Connects to `addr` (reusing the connection across calls) and queues
+    /// the next track the radio server sends, mirroring [`Self::query_track`]
+    /// but reading the track and its [`network::TrackHeader`] off the wire
+    /// instead of from a local file.
+    ///
+    /// Call this repeatedly, e.g. once per [`Self::track_finished`] signal,
+    /// to play an endless interleaved stream of tracks.
+    pub fn query_stream(&mut self, addr: SocketAddr) -> io::Result<QueryTrackResult> {
+        if self.network_stream.is_none() {
+            self.network_stream = Some(TcpStream::connect(addr)?);
+        }
+
+        let header = {
+            let stream = self.network_stream.as_mut().expect("just set it above");
+            network::read_header(stream)?
+        };
+        // A zero sample rate can't reach here: `TrackHeader::sample_rate` is
+        // a `NonZero<u32>`, so `serde` already rejected it while decoding
+        // the header above.
+        let sample_rate = header.sample_rate;
+
+        let same_sample_rate = self
+            .mixer
+            .as_ref()
+            .map(|stream| stream.sample_rate() == sample_rate)
+            .unwrap_or(true);
+
+        if !same_sample_rate && !self.resampling.load(Ordering::Relaxed) {
+            // The header's been consumed but its PCM payload hasn't; there's
+            // no way to discard just that payload without knowing its
+            // length (some tracks are open-ended), so drop the connection
+            // rather than leave the next `query_stream` call reading stale
+            // audio bytes as the next header's length prefix.
+            self.network_stream = None;
+            return Ok(QueryTrackResult::RecreateStreamRequired);
+        }
+
+        let channels = NonZero::new(header.channels).unwrap_or(NonZero::<u16>::MIN);
+        let needs_stream = self.mixer.is_none() || self.player.is_none();
+
+        if needs_stream {
+            let output_stream = backend::open_stream(&self.backend, sample_rate, channels)?;
+
+            let (sender, receiver) = queue(true);
+            let player = rodio::Player::connect_new(output_stream.mixer());
+            player.append(receiver);
+            set_volume(&player, &1.0);
+
+            self.player = Some(player);
+            self.sender = Some(sender);
+            self.mixer = Some(output_stream);
+        }
+
+        // Invalidate any background duration resolution still in flight for
+        // a track queued via `query_track` before this stream took over.
+        self.track_generation.fetch_add(1, Ordering::SeqCst);
+
+        let mixer_sample_rate = self.mixer.as_ref().unwrap().sample_rate();
+        let stream = self
+            .network_stream
+            .as_mut()
+            .expect("still connected, just read a header off it above");
+        let source = NetworkSource::new(stream.try_clone()?, header.clone());
         let track_duration = source.total_duration().unwrap_or_default();
+        let source: BoxedSource = if sample_rate == mixer_sample_rate {
+            Box::new(source)
+        } else {
+            Box::new(LinearResampler::new(source, mixer_sample_rate))
+        };
 
+        let metadata = TrackMetadata {
+            title: Some(header.title),
+            artist: Some(header.artist),
+            album: Some(header.album),
+            duration: track_duration,
+            sample_rate,
+            channels,
+            cover: None,
+        };
+        *self.current_metadata.lock() = Some(metadata.clone());
+        let _ = self.metadata_changed.send(());
+
+        let track_finished = self.track_finished.clone();
         let duration_played = self.duration_played.clone();
         let signal = self.sender.as_ref().unwrap().append_with_signal(source);
 
@@ -148,7 +474,7 @@ impl Sink {
             loop {
                 if signal.try_recv().is_ok() {
                     *duration_played.lock() += track_duration;
-                    track_finished.send(()).expect("infallible");
+                    let _ = track_finished.send(());
                     break;
                 }
                 sleep(Duration::from_millis(200)).await;
@@ -157,7 +483,7 @@ impl Sink {
 
         self.track_handle = Some(track_handle);
 
-        QueryTrackResult::Queued
+        Ok(QueryTrackResult::Queued(metadata))
     }
 
     pub fn sync_volume(&self) {
@@ -172,33 +498,56 @@ fn set_volume(sink: &rodio::Player, volume: &f32) {
     sink.set_volume(volume);
 }
 
-fn open_default_stream(sample_rate: NonZero<u32>) -> rodio::MixerDeviceSink {
-    rodio::DeviceSinkBuilder::from_default_device()
-        .and_then(|x| {
-            x.with_sample_rate(sample_rate)
-                .with_buffer_size(BufferSize::Fixed(1024))
-                .open_stream()
-        })
-        .or_else(|original_err| {
-            let mut devices = rodio::cpal::default_host().output_devices().unwrap();
-
-            devices
-                .find_map(|d| {
-                    rodio::DeviceSinkBuilder::from_device(d)
-                        .and_then(|x| {
-                            x.with_sample_rate(sample_rate)
-                                .with_buffer_size(BufferSize::Fixed(1024))
-                                .open_stream()
-                        })
-                        .ok()
-                })
-                .ok_or(original_err)
-        })
-        .unwrap()
+/// Returns a duration usable right away for a track at `path`: the
+/// container's own figure if it reported one, the cached real figure from a
+/// prior play, or otherwise [`ASSUMED_TRACK_DURATION`] while a background
+/// task decodes the file a second time to count samples and correct
+/// `current_track_duration` and `current_metadata` once it lands.
+///
+/// `generation` is the value of `track_generation` as of the track this call
+/// is resolving for; the background task re-checks it before writing back so
+/// a resolution that finishes after the `Sink` has moved on to a later track
+/// doesn't clobber that track's duration/metadata.
+fn resolve_track_duration(
+    path: &Path,
+    total_duration: Option<Duration>,
+    cache: &DurationCache,
+    generation: u64,
+    track_generation: Arc<AtomicU64>,
+    current_track_duration: Arc<Mutex<Duration>>,
+    current_metadata: Arc<Mutex<Option<TrackMetadata>>>,
+    metadata_changed: Sender<()>,
+) -> Duration {
+    if let Some(duration) = total_duration {
+        return duration;
+    }
+
+    if let Some(duration) = cache.get(path) {
+        return duration;
+    }
+
+    let resolved = cache.resolve(path.to_path_buf());
+    tokio::spawn(async move {
+        let Ok(Some(duration)) = resolved.await else {
+            return;
+        };
+
+        if track_generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+
+        *current_track_duration.lock() = duration;
+        if let Some(metadata) = current_metadata.lock().as_mut() {
+            metadata.duration = duration;
+        }
+        let _ = metadata_changed.send(());
+    });
+
+    ASSUMED_TRACK_DURATION
 }
 
 pub enum QueryTrackResult {
-    Queued,
+    Queued(TrackMetadata),
     RecreateStreamRequired,
 }
 
@@ -211,16 +560,52 @@ impl Drop for Sink {
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
-    file: PathBuf,
+    /// Local file to play. Mutually exclusive with `--stream`.
+    file: Option<PathBuf>,
+
+    /// Radio server to connect to instead of a local file, e.g.
+    /// `127.0.0.1:9000`. Mutually exclusive with `file`.
+    #[clap(long, conflicts_with = "file")]
+    stream: Option<SocketAddr>,
+
+    /// `cpal` (default), `pipe`, or `subprocess:<command>`.
+    #[clap(long, default_value = "cpal")]
+    backend: String,
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
-    let mut sink = Sink::new();
+    let backend = Backend::parse(&cli.backend).unwrap_or_else(|| {
+        eprintln!("unknown backend {:?}, falling back to cpal", cli.backend);
+        Backend::Cpal
+    });
 
-    sink.query_track(&cli.file);
+    let mut sink = Sink::with_backend(backend);
+
+    if let Some(addr) = cli.stream {
+        return run_stream(&mut sink, addr).await;
+    }
+
+    let Some(file) = cli.file else {
+        eprintln!("either a file or --stream <addr> is required");
+        return;
+    };
+
+    match sink.query_track(&file) {
+        Ok(QueryTrackResult::Queued(metadata)) => {
+            println!(
+                "now playing: {}",
+                metadata.title.as_deref().unwrap_or("<unknown>")
+            );
+        }
+        Ok(QueryTrackResult::RecreateStreamRequired) => unreachable!("no stream open yet"),
+        Err(err) => {
+            eprintln!("failed to play {}: {err}", file.display());
+            return;
+        }
+    }
 
     loop {
         println!("position: {}", sink.position().as_secs());
@@ -228,3 +613,35 @@ async fn main() {
         tokio::time::sleep(Duration::from_millis(500)).await;
     }
 }
+
+/// Drives an endless radio stream: queues a track via
+/// [`Sink::query_stream`], waits for [`Sink::track_finished`] to fire, then
+/// queues the next one, looping until the connection errors out.
+async fn run_stream(sink: &mut Sink, addr: SocketAddr) {
+    let mut track_finished = sink.track_finished();
+
+    loop {
+        match sink.query_stream(addr) {
+            Ok(QueryTrackResult::Queued(metadata)) => {
+                println!(
+                    "now playing: {}",
+                    metadata.title.as_deref().unwrap_or("<unknown>")
+                );
+            }
+            Ok(QueryTrackResult::RecreateStreamRequired) => {
+                // The server's sample rate changed and resampling is off;
+                // rebuild the mixer for the new rate and retry immediately.
+                sink.clear();
+                continue;
+            }
+            Err(err) => {
+                eprintln!("stream error: {err}");
+                return;
+            }
+        }
+
+        if track_finished.changed().await.is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::num::NonZero;
+use std::path::Path;
+use std::time::Duration;
+
+use lofty::file::TaggedFileExt;
+use lofty::probe::Probe;
+use lofty::tag::Accessor;
+
+/// Everything we know about the track currently playing, surfaced so a UI
+/// can render a now-playing view instead of just a position counter.
+#[derive(Debug, Clone)]
+pub struct TrackMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Duration,
+    pub sample_rate: NonZero<u32>,
+    pub channels: NonZero<u16>,
+    pub cover: Option<Vec<u8>>,
+}
+
+impl TrackMetadata {
+    /// Reads tags from `path` with `lofty`, falling back to an untagged
+    /// [`TrackMetadata`] carrying just the decoder-reported facts if the
+    /// file has no tags `lofty` recognizes.
+    pub fn read(
+        path: &Path,
+        sample_rate: NonZero<u32>,
+        channels: NonZero<u16>,
+        duration: Duration,
+    ) -> Self {
+        let tagged_file = Probe::open(path).ok().and_then(|probe| probe.read().ok());
+        let tag = tagged_file
+            .as_ref()
+            .and_then(|file| file.primary_tag().or_else(|| file.first_tag()));
+
+        let cover = tag
+            .and_then(|tag| tag.pictures().first())
+            .map(|picture| picture.data().to_vec());
+
+        Self {
+            title: tag.and_then(|tag| tag.title().map(|s| s.to_string())),
+            artist: tag.and_then(|tag| tag.artist().map(|s| s.to_string())),
+            album: tag.and_then(|tag| tag.album().map(|s| s.to_string())),
+            duration,
+            sample_rate,
+            channels,
+            cover,
+        }
+    }
+}
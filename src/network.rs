@@ -0,0 +1,106 @@
+use std::io::{self, Read};
+use std::mem::size_of;
+use std::net::TcpStream;
+use std::num::NonZero;
+use std::time::Duration;
+
+use rodio::Source;
+use serde::Deserialize;
+
+/// MessagePack header sent by the radio server ahead of each track's raw
+/// audio payload.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrackHeader {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    /// `serde`'s `NonZeroU32` impl rejects a `0` while deserializing, so a
+    /// malformed or malicious header fails in [`read_header`] with an
+    /// `io::Error` instead of panicking once played.
+    pub sample_rate: NonZero<u32>,
+    pub channels: u16,
+    /// Length of the track in samples per channel, when the server knows it
+    /// up front. `None` for a live feed with no defined end.
+    pub track_length_samples: Option<u64>,
+}
+
+/// Reads the next length-prefixed MessagePack header off `stream`.
+///
+/// The server frames each header as a big-endian `u32` byte length followed
+/// by that many bytes of MessagePack-encoded [`TrackHeader`].
+pub(crate) fn read_header(stream: &mut TcpStream) -> io::Result<TrackHeader> {
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf)?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+
+    let mut buf = vec![0u8; len];
+    stream.read_exact(&mut buf)?;
+
+    rmp_serde::from_slice(&buf).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// A [`Source`] that streams one track's worth of PCM audio off a TCP
+/// connection, stopping once `header.track_length_samples` samples per channel
+/// (that many times `header.channels` interleaved samples) have been read (or
+/// never, if the server didn't report a length).
+pub struct NetworkSource {
+    stream: TcpStream,
+    header: TrackHeader,
+    samples_read: u64,
+}
+
+impl NetworkSource {
+    pub(crate) fn new(stream: TcpStream, header: TrackHeader) -> Self {
+        Self {
+            stream,
+            header,
+            samples_read: 0,
+        }
+    }
+
+    pub fn header(&self) -> &TrackHeader {
+        &self.header
+    }
+
+    fn exhausted(&self) -> bool {
+        self.header
+            .track_length_samples
+            .is_some_and(|total| self.samples_read >= total * self.header.channels as u64)
+    }
+}
+
+impl Iterator for NetworkSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.exhausted() {
+            return None;
+        }
+
+        let mut buf = [0u8; size_of::<i16>()];
+        self.stream.read_exact(&mut buf).ok()?;
+        self.samples_read += 1;
+        Some(i16::from_le_bytes(buf))
+    }
+}
+
+impl Source for NetworkSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> NonZero<u16> {
+        NonZero::new(self.header.channels).unwrap_or(NonZero::<u16>::MIN)
+    }
+
+    fn sample_rate(&self) -> NonZero<u32> {
+        self.header.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let samples = self.header.track_length_samples?;
+        Some(Duration::from_secs_f64(
+            samples as f64 / self.header.sample_rate.get() as f64,
+        ))
+    }
+}
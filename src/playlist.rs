@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rand::seq::SliceRandom;
+
+/// How the playlist advances past the current track: `Off` stops at the end
+/// of `play_order`, `One` repeats the current track, `All` wraps back to the
+/// start of `play_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    One,
+    All,
+}
+
+#[derive(Default)]
+struct PlaylistState {
+    /// Tracks in the order they were enqueued.
+    order: Vec<PathBuf>,
+    /// Indices into `order` describing the order tracks are actually played
+    /// in; sequential when `shuffle` is off, a Fisher-Yates permutation of
+    /// `order` when it's on.
+    play_order: Vec<usize>,
+    /// Position within `play_order`, not an index into `order`.
+    current: Option<usize>,
+    repeat: RepeatMode,
+    shuffle: bool,
+}
+
+impl PlaylistState {
+    /// Rebuilds `play_order` for the current `shuffle` setting, keeping
+    /// `current` pointing at the same track so toggling shuffle doesn't
+    /// interrupt playback.
+    fn rebuild_play_order(&mut self) {
+        let current_track = self
+            .current
+            .and_then(|position| self.play_order.get(position))
+            .copied();
+
+        self.play_order = (0..self.order.len()).collect();
+        if self.shuffle {
+            self.play_order.shuffle(&mut rand::rng());
+        }
+
+        self.current = current_track
+            .and_then(|track| self.play_order.iter().position(|&index| index == track));
+    }
+}
+
+/// An ordered list of tracks with shuffle and repeat support.
+///
+/// Cheaply cloneable: clones share the same underlying state, so the
+/// background track-advance task can hold one alongside [`crate::Sink`].
+#[derive(Clone, Default)]
+pub struct Playlist {
+    state: Arc<Mutex<PlaylistState>>,
+}
+
+impl Playlist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enqueue(&self, paths: impl IntoIterator<Item = PathBuf>) {
+        let mut state = self.state.lock();
+        state.order.extend(paths);
+        state.rebuild_play_order();
+    }
+
+    /// The track at the current position, without advancing.
+    pub fn current(&self) -> Option<PathBuf> {
+        let state = self.state.lock();
+        let position = state.current?;
+        let index = *state.play_order.get(position)?;
+        state.order.get(index).cloned()
+    }
+
+    /// Advances to the next track per the current [`RepeatMode`] and returns
+    /// it, or `None` if the playlist is empty or has reached its end.
+    pub fn advance_next(&self) -> Option<PathBuf> {
+        let mut state = self.state.lock();
+        if state.play_order.is_empty() {
+            return None;
+        }
+
+        state.current = match state.current {
+            None => Some(0),
+            Some(position) if state.repeat == RepeatMode::One => Some(position),
+            Some(position) => {
+                let next = position + 1;
+                if next < state.play_order.len() {
+                    Some(next)
+                } else if state.repeat == RepeatMode::All {
+                    Some(0)
+                } else {
+                    None
+                }
+            }
+        };
+
+        let position = state.current?;
+        let index = state.play_order[position];
+        state.order.get(index).cloned()
+    }
+
+    /// Steps back one track and returns it, or `None` if already at the
+    /// start (unless [`RepeatMode::All`] wraps it to the end).
+    pub fn advance_previous(&self) -> Option<PathBuf> {
+        let mut state = self.state.lock();
+        let position = state.current?;
+
+        let previous = match position.checked_sub(1) {
+            Some(previous) => previous,
+            None if state.repeat == RepeatMode::All => state.play_order.len().checked_sub(1)?,
+            None => return None,
+        };
+
+        state.current = Some(previous);
+        let index = state.play_order[previous];
+        state.order.get(index).cloned()
+    }
+
+    pub fn set_repeat(&self, repeat: RepeatMode) {
+        self.state.lock().repeat = repeat;
+    }
+
+    pub fn set_shuffle(&self, shuffle: bool) {
+        let mut state = self.state.lock();
+        state.shuffle = shuffle;
+        state.rebuild_play_order();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist(len: usize) -> Playlist {
+        let playlist = Playlist::new();
+        playlist.enqueue((0..len).map(|i| PathBuf::from(format!("track{i}"))));
+        playlist
+    }
+
+    #[test]
+    fn advance_next_walks_in_order() {
+        let playlist = playlist(3);
+        assert_eq!(playlist.advance_next(), Some(PathBuf::from("track0")));
+        assert_eq!(playlist.advance_next(), Some(PathBuf::from("track1")));
+        assert_eq!(playlist.advance_next(), Some(PathBuf::from("track2")));
+        assert_eq!(playlist.advance_next(), None);
+    }
+
+    #[test]
+    fn advance_previous_steps_back() {
+        let playlist = playlist(3);
+        playlist.advance_next();
+        playlist.advance_next();
+        assert_eq!(playlist.advance_previous(), Some(PathBuf::from("track0")));
+        assert_eq!(playlist.advance_previous(), None);
+    }
+
+    #[test]
+    fn repeat_all_wraps_in_both_directions() {
+        let playlist = playlist(2);
+        playlist.set_repeat(RepeatMode::All);
+        playlist.advance_next();
+        playlist.advance_next();
+        assert_eq!(playlist.advance_next(), Some(PathBuf::from("track0")));
+        assert_eq!(playlist.advance_previous(), Some(PathBuf::from("track1")));
+    }
+}
@@ -0,0 +1,180 @@
+use std::num::NonZero;
+use std::time::Duration;
+
+use rodio::Source;
+
+/// Resamples a decoded [`Source`] to `target_rate` by linear interpolation
+/// between the two bracketing input frames per channel, so a track can keep
+/// flowing into a mixer whose sample rate it doesn't natively match instead
+/// of requiring the output stream to be rebuilt.
+pub struct LinearResampler<S> {
+    input: S,
+    channels: usize,
+    target_rate: NonZero<u32>,
+    rate_ratio: f64,
+    /// Fractional position of the next output sample within the current
+    /// input frame pair; advances by `rate_ratio` per output frame (once all
+    /// channels of that frame have been emitted).
+    pos: f64,
+    current_frame: Vec<i16>,
+    current_has_data: bool,
+    next_frame: Vec<i16>,
+    next_has_data: bool,
+    channel_cursor: usize,
+    exhausted: bool,
+}
+
+impl<S: Source<Item = i16>> LinearResampler<S> {
+    pub fn new(mut input: S, target_rate: NonZero<u32>) -> Self {
+        let channels = input.channels().get() as usize;
+        let rate_ratio = input.sample_rate().get() as f64 / target_rate.get() as f64;
+
+        let (current_frame, current_has_data) = Self::read_frame(&mut input, channels);
+        let (next_frame, next_has_data) = Self::read_frame(&mut input, channels);
+
+        Self {
+            input,
+            channels,
+            target_rate,
+            rate_ratio,
+            pos: 0.0,
+            current_frame,
+            current_has_data,
+            next_frame,
+            next_has_data,
+            channel_cursor: 0,
+            exhausted: !current_has_data,
+        }
+    }
+
+    fn read_frame(input: &mut S, channels: usize) -> (Vec<i16>, bool) {
+        let mut frame = vec![0i16; channels];
+        let mut has_data = false;
+        for slot in frame.iter_mut() {
+            if let Some(sample) = input.next() {
+                *slot = sample;
+                has_data = true;
+            }
+        }
+        (frame, has_data)
+    }
+
+    fn interpolated(&self, channel: usize) -> i16 {
+        let a = self.current_frame[channel] as f64;
+        let b = self.next_frame[channel] as f64;
+        (a + (b - a) * self.pos) as i16
+    }
+}
+
+impl<S: Source<Item = i16>> Iterator for LinearResampler<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.exhausted {
+            return None;
+        }
+
+        while self.pos >= 1.0 {
+            std::mem::swap(&mut self.current_frame, &mut self.next_frame);
+            self.current_has_data = self.next_has_data;
+            (self.next_frame, self.next_has_data) = Self::read_frame(&mut self.input, self.channels);
+            self.pos -= 1.0;
+
+            if !self.current_has_data {
+                self.exhausted = true;
+                return None;
+            }
+        }
+
+        let sample = self.interpolated(self.channel_cursor);
+        self.channel_cursor += 1;
+
+        if self.channel_cursor == self.channels {
+            self.channel_cursor = 0;
+            self.pos += self.rate_ratio;
+        }
+
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = i16>> Source for LinearResampler<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> NonZero<u16> {
+        NonZero::new(self.channels as u16).unwrap_or(NonZero::<u16>::MIN)
+    }
+
+    fn sample_rate(&self) -> NonZero<u32> {
+        self.target_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixed, mono sample sequence at a fixed rate, just enough to drive
+    /// [`LinearResampler`] without pulling in a real decoder.
+    struct FixedSource {
+        samples: std::vec::IntoIter<i16>,
+        sample_rate: NonZero<u32>,
+    }
+
+    impl FixedSource {
+        fn new(samples: Vec<i16>, sample_rate: u32) -> Self {
+            Self {
+                samples: samples.into_iter(),
+                sample_rate: NonZero::new(sample_rate).unwrap(),
+            }
+        }
+    }
+
+    impl Iterator for FixedSource {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<i16> {
+            self.samples.next()
+        }
+    }
+
+    impl Source for FixedSource {
+        fn current_span_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> NonZero<u16> {
+            NonZero::<u16>::MIN
+        }
+
+        fn sample_rate(&self) -> NonZero<u32> {
+            self.sample_rate
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    #[test]
+    fn passthrough_when_rate_matches() {
+        let input = FixedSource::new(vec![1, 2, 3, 4], 100);
+        let resampled: Vec<i16> =
+            LinearResampler::new(input, NonZero::new(100).unwrap()).collect();
+        assert_eq!(resampled, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn doubling_the_rate_roughly_doubles_the_sample_count() {
+        let input = FixedSource::new(vec![0, 100, 200, 300, 400, 500], 50);
+        let resampled: Vec<i16> =
+            LinearResampler::new(input, NonZero::new(100).unwrap()).collect();
+        assert!(resampled.len() >= 10);
+    }
+}